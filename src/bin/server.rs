@@ -48,16 +48,37 @@ fn tracing_setup() {
 }
 
 async fn entrypoint(addr: &SocketAddr, conn_opts: PgConnectOptions) -> anyhow::Result<()> {
-    let shared = handlers::Shared::builder()
-        .with_connect_opts(conn_opts)
-        .with_max_cache_capacity(1000)
-        .build()
-        .await?;
+    // A `SLED_PATH` selects the self-contained embedded backend, otherwise we
+    // fall back to the Postgres pool built from `DATABASE_URL`.
+    match env::var("SLED_PATH") {
+        Ok(path) => {
+            let shared = handlers::Shared::builder()
+                .with_sled_path(path)?
+                .with_max_cache_capacity(1000)
+                .build()
+                .await?;
+            serve(addr, shared).await
+        }
+        Err(_) => {
+            let shared = handlers::Shared::builder()
+                .with_connect_opts(conn_opts)
+                .with_max_cache_capacity(1000)
+                .build()
+                .await?;
+            serve(addr, shared).await
+        }
+    }
+}
+
+async fn serve<S>(addr: &SocketAddr, shared: handlers::Shared<S>) -> anyhow::Result<()>
+where
+    S: handlers::store::Store + Clone,
+{
     let routes = Router::new()
-        .route("/:slug", get(handlers::resolve))
-        .route("/rev/:url", get(handlers::reverse))
-        .route("/post/:url", post(handlers::generate))
-        .route("/metrics", get(handlers::metrics))
+        .route("/:slug", get(handlers::resolve::<S>))
+        .route("/rev/:url", get(handlers::reverse::<S>))
+        .route("/post/:url", post(handlers::generate::<S>))
+        .route("/metrics", get(handlers::metrics::<S>))
         .with_state(shared)
         .layer(CorsLayer::permissive())
         .into_make_service();
@@ -1,28 +1,26 @@
-use std::sync::Arc;
+use std::{path::Path as FsPath, sync::Arc};
 
 use anyhow::Context;
-use sqlx::{
-    postgres::{PgConnectOptions, PgPoolOptions},
-    PgPool,
-};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 
 use crate::{metrics::Registry, slug::Slug};
 
 use self::states::*;
+use super::store::{PostgresStore, SledStore, Store};
 
 pub(super) type Cache = moka::sync::Cache<Slug, Arc<str>, ahash::RandomState>;
 
 #[derive(Clone)]
-pub struct Shared {
-    pub(super) pool: PgPool,
+pub struct Shared<S> {
+    pub(super) store: S,
     pub(super) cache: Cache,
     pub(super) registry: Registry,
 }
 
-impl Shared {
+impl Shared<PostgresStore> {
     #[inline]
     pub fn builder() -> Builder<Init> {
-        Builder::default()
+        Builder { state: Init }
     }
 }
 
@@ -30,56 +28,62 @@ pub struct Builder<S> {
     state: S,
 }
 
-impl Default for Builder<Init> {
-    fn default() -> Self {
-        Builder { state: Init }
-    }
-}
-
 impl Builder<Init> {
+    /// Back the shared state with a Postgres connection pool.
     #[inline]
-    pub fn with_connect_opts(self, opts: PgConnectOptions) -> Builder<HasPool> {
-        let pool = PgPoolOptions::new()
-            .min_connections(1)
-            .max_connections(2)
-            .connect_with(opts);
+    pub fn with_connect_opts(self, opts: PgConnectOptions) -> Builder<HasStore<PostgresStore>> {
+        let store = Box::pin(async move {
+            let pool = PgPoolOptions::new()
+                .min_connections(1)
+                .max_connections(2)
+                .connect_with(opts)
+                .await
+                .context("unable to establish database connection")?;
+            sqlx::migrate!()
+                .run(&pool)
+                .await
+                .context("unable to run database migrations")?;
+            anyhow::Ok(PostgresStore::new(pool))
+        });
         Builder {
-            state: HasPool {
-                pool: Box::pin(pool),
+            state: HasStore {
+                store,
+                cache_capacity: 1000,
             },
         }
     }
-}
 
-impl Builder<HasPool> {
+    /// Back the shared state with a self-contained embedded sled database.
     #[inline]
-    pub fn with_max_cache_capacity(self, capacity: u64) -> Builder<HasCache> {
-        let cache = moka::sync::Cache::builder()
-            .max_capacity(capacity)
-            .build_with_hasher(ahash::RandomState::new());
-        Builder {
-            state: HasCache {
-                pool: self.state.pool,
-                cache,
+    pub fn with_sled_path(
+        self,
+        path: impl AsRef<FsPath>,
+    ) -> anyhow::Result<Builder<HasStore<SledStore>>> {
+        let store = SledStore::open(path)?;
+        Ok(Builder {
+            state: HasStore {
+                store: Box::pin(std::future::ready(anyhow::Ok(store))),
+                cache_capacity: 1000,
             },
-        }
+        })
     }
 }
 
-impl Builder<HasCache> {
-    pub async fn build(self) -> anyhow::Result<Shared> {
-        let pool = self
-            .state
-            .pool
-            .await
-            .context("unable to establish database connection")?;
-        sqlx::migrate!()
-            .run(&pool)
-            .await
-            .context("unable to run database migrations")?;
+impl<S: Store> Builder<HasStore<S>> {
+    #[inline]
+    pub fn with_max_cache_capacity(mut self, capacity: u64) -> Self {
+        self.state.cache_capacity = capacity;
+        self
+    }
+
+    pub async fn build(self) -> anyhow::Result<Shared<S>> {
+        let store = self.state.store.await?;
+        let cache = moka::sync::Cache::builder()
+            .max_capacity(self.state.cache_capacity)
+            .build_with_hasher(ahash::RandomState::new());
         Ok(Shared {
-            pool,
-            cache: self.state.cache,
+            store,
+            cache,
             registry: Registry::default(),
         })
     }
@@ -95,13 +99,8 @@ mod states {
     pub struct Init;
 
     #[doc(hidden)]
-    pub struct HasPool {
-        pub(super) pool: Pin<Box<dyn Future<Output = sqlx::Result<PgPool>>>>,
-    }
-
-    #[doc(hidden)]
-    pub struct HasCache {
-        pub(super) pool: Pin<Box<dyn Future<Output = sqlx::Result<PgPool>>>>,
-        pub(super) cache: Cache,
+    pub struct HasStore<S> {
+        pub(super) store: Pin<Box<dyn Future<Output = anyhow::Result<S>>>>,
+        pub(super) cache_capacity: u64,
     }
 }
@@ -0,0 +1,193 @@
+use std::{future::Future, sync::Arc};
+
+use crate::slug::Slug;
+
+/// Reason an [`insert`](Store::insert) could not be committed.
+///
+/// `generate` relies on telling a slug collision (retryable) apart from a url
+/// conflict (a `409` for the caller), so the two uniqueness violations get
+/// their own variants; everything else is an opaque backend failure.
+pub enum InsertError {
+    /// The slug is already taken (`links_pkey`), retry with a fresh one.
+    SlugCollision,
+    /// The url is already registered under another slug (`links_url_key`).
+    UrlConflict,
+    /// The backend failed for some other reason.
+    Backend(anyhow::Error),
+}
+
+/// The storage operations the handlers actually need.
+///
+/// Abstracting these lets the crate run either against Postgres or the
+/// self-contained embedded [`SledStore`], selected through the builder.
+///
+/// The methods spell out `-> impl Future + Send` rather than using bare
+/// `async fn` so the futures are usable from axum's `Send`-bounded handlers.
+pub trait Store: Send + Sync + 'static {
+    /// Insert a new `slug -> url` mapping, enforcing both the slug and url
+    /// uniqueness constraints.
+    fn insert(&self, slug: Slug, url: &str) -> impl Future<Output = Result<(), InsertError>> + Send;
+
+    /// Resolve a slug to its url, or `None` if unknown.
+    fn resolve(&self, slug: Slug) -> impl Future<Output = Option<Arc<str>>> + Send;
+
+    /// Reverse lookup the slug a url was registered under.
+    fn reverse(&self, url: &str) -> impl Future<Output = Option<Slug>> + Send;
+
+    /// Allocate a fresh slug for `url`, retrying on slug collisions.
+    fn generate(&self, url: &str) -> impl Future<Output = Result<Slug, InsertError>> + Send {
+        async move {
+            let mut retries = 0;
+            loop {
+                let slug = Slug::from_rng(&mut rand::thread_rng());
+                match self.insert(slug, url).await {
+                    Ok(()) => return Ok(slug),
+                    Err(InsertError::SlugCollision) if retries < 2 => {
+                        tracing::debug!(%retries, "retrying");
+                        retries += 1;
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+/// [`Store`] backed by a Postgres connection pool.
+#[derive(Clone)]
+pub struct PostgresStore {
+    pool: sqlx::PgPool,
+}
+
+impl PostgresStore {
+    #[inline]
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl Store for PostgresStore {
+    async fn insert(&self, slug: Slug, url: &str) -> Result<(), InsertError> {
+        let result = sqlx::query!(
+            "INSERT INTO links (slug, url) VALUES ($1, $2)",
+            slug.as_str(),
+            url,
+        )
+        .execute(&self.pool)
+        .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(match e.as_database_error().and_then(|e| e.constraint()) {
+                Some("links_pkey") => InsertError::SlugCollision,
+                Some("links_url_key") => InsertError::UrlConflict,
+                _ => InsertError::Backend(e.into()),
+            }),
+        }
+    }
+
+    async fn resolve(&self, slug: Slug) -> Option<Arc<str>> {
+        let url = sqlx::query_scalar!("SELECT url FROM links WHERE slug = $1", slug.as_str())
+            .fetch_optional(&self.pool)
+            .await;
+
+        match url {
+            Ok(url) => url.map(Into::into),
+            Err(e) => {
+                tracing::error!(cause = %e, "unable to resolve slug");
+                None
+            }
+        }
+    }
+
+    async fn reverse(&self, url: &str) -> Option<Slug> {
+        let slug = sqlx::query_scalar!("SELECT slug FROM links WHERE url = $1", url)
+            .fetch_optional(&self.pool)
+            .await;
+
+        match slug {
+            // Slugs should always be correct length.
+            Ok(slug) => slug.map(|slug| Slug::try_from(slug.as_str()).unwrap()),
+            Err(e) => {
+                tracing::error!(cause = %e, "unable to reverse lookup");
+                None
+            }
+        }
+    }
+}
+
+/// Self-contained [`Store`] backed by an embedded [`sled`] database.
+///
+/// Two keyspaces are kept in lockstep: `slugs` maps a slug to its url and
+/// `urls` maps a url back to its slug. Inserts touch both inside a single
+/// transaction so the slug- and url-uniqueness invariants hold atomically,
+/// mirroring the `links_pkey`/`links_url_key` constraints.
+#[derive(Clone)]
+pub struct SledStore {
+    slugs: sled::Tree,
+    urls: sled::Tree,
+}
+
+/// Abort reason threaded out of the insert transaction.
+enum Abort {
+    SlugCollision,
+    UrlConflict,
+}
+
+impl SledStore {
+    pub fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let db = sled::open(path)?;
+        let slugs = db.open_tree("slugs")?;
+        let urls = db.open_tree("urls")?;
+        Ok(Self { slugs, urls })
+    }
+}
+
+impl Store for SledStore {
+    async fn insert(&self, slug: Slug, url: &str) -> Result<(), InsertError> {
+        use sled::transaction::{abort, TransactionError};
+
+        let result = (&self.slugs, &self.urls).transaction(|(slugs, urls)| {
+            if slugs.get(slug.as_str().as_bytes())?.is_some() {
+                return abort(Abort::SlugCollision);
+            }
+            if urls.get(url.as_bytes())?.is_some() {
+                return abort(Abort::UrlConflict);
+            }
+            slugs.insert(slug.as_str().as_bytes(), url.as_bytes())?;
+            urls.insert(url.as_bytes(), slug.as_str().as_bytes())?;
+            Ok(())
+        });
+
+        result.map_err(|e| match e {
+            TransactionError::Abort(Abort::SlugCollision) => InsertError::SlugCollision,
+            TransactionError::Abort(Abort::UrlConflict) => InsertError::UrlConflict,
+            TransactionError::Storage(e) => InsertError::Backend(e.into()),
+        })
+    }
+
+    async fn resolve(&self, slug: Slug) -> Option<Arc<str>> {
+        match self.slugs.get(slug.as_str().as_bytes()) {
+            Ok(Some(value)) => Some(Arc::from(String::from_utf8_lossy(&value).as_ref())),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::error!(cause = %e, "unable to resolve slug");
+                None
+            }
+        }
+    }
+
+    async fn reverse(&self, url: &str) -> Option<Slug> {
+        match self.urls.get(url.as_bytes()) {
+            // Slugs should always be correct length.
+            Ok(slug) => {
+                slug.map(|slug| Slug::try_from(String::from_utf8_lossy(&slug).as_ref()).unwrap())
+            }
+            Err(e) => {
+                tracing::error!(cause = %e, "unable to reverse lookup");
+                None
+            }
+        }
+    }
+}
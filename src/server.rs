@@ -1,8 +1,9 @@
 #![warn(unreachable_pub)]
 
-use std::net::SocketAddr;
+use std::{env, net::SocketAddr};
 
 use axum::{
+    middleware,
     routing::{get, post},
     Router, Server,
 };
@@ -14,21 +15,98 @@ use tower_http::{
 };
 use tracing::Level;
 
+mod auth;
 mod handlers;
 mod metrics;
+mod redis;
 mod slug;
+mod store;
 
 #[tracing::instrument(level = "trace")]
 pub(crate) async fn run(addr: &SocketAddr, opts: PgConnectOptions) -> color_eyre::Result<()> {
-    let shared = handlers::Shared::default_settings(opts).await?;
+    // A `SLED_PATH` selects the self-contained embedded backend, otherwise we
+    // fall back to the Postgres pool built from `DATABASE_URL`.
+    let redis_url = env::var("REDIS_URL").ok();
+    match env::var("SLED_PATH") {
+        Ok(path) => {
+            let mut builder = handlers::Shared::builder()
+                .with_sled_path(path)?
+                .with_max_cache_capacity(1000);
+            if let Some(url) = redis_url {
+                builder = builder.with_redis(url);
+            }
+            serve(addr, builder.build().await?).await
+        }
+        Err(_) => {
+            let mut builder = handlers::Shared::builder()
+                .with_connect_opts(opts)
+                .with_max_cache_capacity(1000);
+            if let Some(url) = redis_url {
+                builder = builder.with_redis(url);
+            }
+            serve(addr, builder.build().await?).await
+        }
+    }
+}
+
+async fn serve<S: store::Store>(
+    addr: &SocketAddr,
+    shared: &'static handlers::Shared<S>,
+) -> color_eyre::Result<()> {
+    // Periodically drop links whose expiry has passed so the table doesn't grow
+    // unbounded; backends without expiry support treat this as a no-op.
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            shared.store().delete_expired().await;
+        }
+    });
+
+    // Only guard the write/admin routes when the backend actually has a key
+    // store; the embedded sled backend has none, so it stays usable without
+    // provisioning any keys.
+    let guarded = shared.store().has_key_store();
+
+    let register = {
+        let r = post(handlers::register);
+        match guarded {
+            true => r.route_layer(middleware::from_fn_with_state(
+                shared,
+                auth::require_register::<S>,
+            )),
+            false => r,
+        }
+    };
+    let batch = {
+        let r = post(handlers::batch);
+        match guarded {
+            true => r.route_layer(middleware::from_fn_with_state(
+                shared,
+                auth::require_register::<S>,
+            )),
+            false => r,
+        }
+    };
+    let admin = {
+        let r = Router::new()
+            .route("/metrics", get(handlers::admin_metrics))
+            .route("/events", get(handlers::admin_events));
+        match guarded {
+            true => r.route_layer(middleware::from_fn_with_state(
+                shared,
+                auth::require_admin::<S>,
+            )),
+            false => r,
+        }
+    };
+
     let routes = Router::new()
         .route("/:slug", get(handlers::resolve))
         .route("/rev/:url", get(handlers::reverse))
-        .route("/reg", post(handlers::register))
-        .nest(
-            "/admin",
-            Router::new().route("/metrics", get(handlers::admin_metrics)),
-        )
+        .route("/reg", register)
+        .route("/batch", batch)
+        .nest("/admin", admin)
         .with_state(shared)
         .layer(CorsLayer::permissive())
         .layer(
@@ -0,0 +1,146 @@
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header::AUTHORIZATION, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use super::{handlers::Shared, metrics::AuthLabels, store::Store};
+
+/// A capability a route requires of the caller's API key.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Scope {
+    Register,
+    Admin,
+}
+
+impl Scope {
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            Scope::Register => "register",
+            Scope::Admin => "admin",
+        }
+    }
+
+    pub(super) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "register" => Some(Scope::Register),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// Why an otherwise well-formed request was turned away, used both as the
+/// `reason` metric label and to pick the status code.
+#[derive(Clone, Copy)]
+enum AuthFailure {
+    /// No (or malformed) `Authorization: Bearer` header.
+    Missing,
+    /// The key is not in the `api_keys` table.
+    Unknown,
+    /// `now` is before the key's `not_before`.
+    NotYetValid,
+    /// `now` is at or after the key's `not_after`.
+    Expired,
+    /// The key is valid but its scopes don't cover this route.
+    Scope,
+}
+
+impl AuthFailure {
+    fn as_str(self) -> &'static str {
+        match self {
+            AuthFailure::Missing => "missing",
+            AuthFailure::Unknown => "unknown",
+            AuthFailure::NotYetValid => "not_yet_valid",
+            AuthFailure::Expired => "expired",
+            AuthFailure::Scope => "scope",
+        }
+    }
+
+    fn status_code(self) -> StatusCode {
+        match self {
+            // An absent or unrecognised key is an authentication problem.
+            AuthFailure::Missing | AuthFailure::Unknown => StatusCode::UNAUTHORIZED,
+            // A known key that simply isn't allowed (yet) is a scope/time problem.
+            AuthFailure::NotYetValid | AuthFailure::Expired | AuthFailure::Scope => {
+                StatusCode::FORBIDDEN
+            }
+        }
+    }
+}
+
+/// Middleware guarding the link-creating routes (`register` and `batch`).
+pub(super) async fn require_register<S: Store>(
+    State(shared): State<&'static Shared<S>>,
+    req: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    gate(shared, Scope::Register, req, next).await
+}
+
+/// Middleware guarding the admin routes.
+pub(super) async fn require_admin<S: Store>(
+    State(shared): State<&'static Shared<S>>,
+    req: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    gate(shared, Scope::Admin, req, next).await
+}
+
+async fn gate<S: Store>(
+    shared: &'static Shared<S>,
+    required: Scope,
+    req: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    match authorize(shared, required, &req).await {
+        Ok(()) => next.run(req).await,
+        Err(failure) => {
+            shared
+                .metrics()
+                .auth_failures
+                .get_or_create(&AuthLabels {
+                    reason: failure.as_str(),
+                })
+                .inc();
+            failure.status_code().into_response()
+        }
+    }
+}
+
+async fn authorize<S: Store>(
+    shared: &'static Shared<S>,
+    required: Scope,
+    req: &Request<Body>,
+) -> Result<(), AuthFailure> {
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AuthFailure::Missing)?;
+
+    // Keys are stored hashed, never in plaintext.
+    let hash = Sha256::digest(token.as_bytes());
+    let key = shared
+        .store()
+        .lookup_key(&hash)
+        .await
+        .ok_or(AuthFailure::Unknown)?;
+
+    let now = chrono::Utc::now();
+    if now < key.not_before {
+        return Err(AuthFailure::NotYetValid);
+    }
+    if now >= key.not_after {
+        return Err(AuthFailure::Expired);
+    }
+    if !key.scopes.contains(&required) {
+        return Err(AuthFailure::Scope);
+    }
+
+    Ok(())
+}
@@ -1,62 +1,205 @@
-use std::sync::Arc;
+use std::{
+    future::Future,
+    path::Path as FsPath,
+    pin::Pin,
+    time::{Duration, Instant},
+};
 
 use axum::{
     body::StreamBody,
-    debug_handler,
-    extract::{Form, Host, Path, State},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Form, Host, Path, State,
+    },
     http::StatusCode,
     response::{Html, IntoResponse, Redirect, Response},
+    Json,
 };
+use chrono::{DateTime, Utc};
 use futures_util::{Stream, TryStreamExt};
 use serde::{Deserialize, Serialize};
-use sqlx::{
-    postgres::{PgConnectOptions, PgPoolOptions},
-    PgPool,
-};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use tokio::sync::broadcast;
 use tracing::instrument;
 use url::Url;
 
 use crate::server::metrics::Labels;
 
-use super::{metrics::Metrics, slug::Slug};
+use self::states::*;
+use super::{
+    metrics::Metrics,
+    redis::RedisCache,
+    slug::Slug,
+    store::{Entry, InsertError, PostgresStore, SledStore, Store},
+};
+
+pub(super) type Cache = moka::sync::Cache<Slug, Entry, ahash::RandomState>;
+
+// Per-entry moka expiry: an entry with an `expires_at` lives only until then.
+struct ExpiryPolicy;
+
+impl moka::Expiry<Slug, Entry> for ExpiryPolicy {
+    fn expire_after_create(
+        &self,
+        _slug: &Slug,
+        (_, _, expires_at): &Entry,
+        _created: Instant,
+    ) -> Option<Duration> {
+        expires_at.map(|at| (at - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+    }
+}
 
-type Cache = moka::sync::Cache<Slug, (Arc<str>, bool), ahash::RandomState>;
+/// Compute an absolute expiry from an optional TTL in seconds, saturating at
+/// the far future rather than overflowing on an absurdly large value.
+fn expiry_from_ttl(ttl: Option<u64>) -> Option<DateTime<Utc>> {
+    ttl.map(|secs| {
+        // `chrono::Duration::seconds` panics beyond `i64::MAX / 1000` seconds.
+        let secs = i64::try_from(secs).unwrap_or(i64::MAX).min(i64::MAX / 1000);
+        Utc::now()
+            .checked_add_signed(chrono::Duration::seconds(secs))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    })
+}
+
+/// Whether an entry's expiry (if any) has already passed.
+fn is_expired(expires_at: Option<DateTime<Utc>>) -> bool {
+    expires_at.is_some_and(|at| at <= Utc::now())
+}
+
+/// A newly created link, broadcast to every `/admin/events` subscriber.
+#[derive(Clone, Serialize)]
+pub(super) struct Event {
+    slug: String,
+    url: String,
+    hidden: bool,
+}
 
 // Shared state required by all handlers.
-pub(super) struct Shared {
-    pool: PgPool,
+pub(super) struct Shared<S> {
+    store: S,
     cache: Cache,
     metrics: Metrics,
+    // Optional L2 cache; `None` for single-node deploys.
+    redis: Option<RedisCache>,
+    // Live feed of newly created links for `/admin/events` subscribers.
+    events: broadcast::Sender<Event>,
 }
 
-impl Shared {
-    pub(super) async fn default_settings(
+impl Shared<PostgresStore> {
+    #[inline]
+    pub(super) fn builder() -> Builder<Init> {
+        Builder { state: Init }
+    }
+}
+
+impl<S> Shared<S> {
+    #[inline]
+    pub(super) fn store(&self) -> &S {
+        &self.store
+    }
+
+    #[inline]
+    pub(super) fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+}
+
+pub(super) struct Builder<S> {
+    state: S,
+}
+
+impl Builder<Init> {
+    /// Back the shared state with a Postgres connection pool.
+    pub(super) fn with_connect_opts(
+        self,
         opts: PgConnectOptions,
-    ) -> color_eyre::Result<&'static Self> {
-        let pool = PgPoolOptions::new()
-            .min_connections(1)
-            .max_connections(10)
-            .connect_with(opts)
-            .await?;
+    ) -> Builder<HasStore<PostgresStore>> {
+        let store = Box::pin(async move {
+            let pool = PgPoolOptions::new()
+                .min_connections(1)
+                .max_connections(10)
+                .connect_with(opts)
+                .await?;
+            sqlx::migrate!().run(&pool).await?;
+            color_eyre::Result::Ok(PostgresStore::new(pool))
+        });
+        Builder {
+            state: HasStore {
+                store,
+                cache_capacity: 1000,
+                redis_url: None,
+            },
+        }
+    }
+
+    /// Back the shared state with a self-contained embedded sled database.
+    pub(super) fn with_sled_path(
+        self,
+        path: impl AsRef<FsPath>,
+    ) -> color_eyre::Result<Builder<HasStore<SledStore>>> {
+        let store = SledStore::open(path)?;
+        Ok(Builder {
+            state: HasStore {
+                store: Box::pin(std::future::ready(Ok(store))),
+                cache_capacity: 1000,
+                redis_url: None,
+            },
+        })
+    }
+}
+
+impl<S: Store> Builder<HasStore<S>> {
+    #[inline]
+    pub(super) fn with_max_cache_capacity(mut self, capacity: u64) -> Self {
+        self.state.cache_capacity = capacity;
+        self
+    }
+
+    /// Enable the optional Redis L2 cache and cross-instance invalidation bus.
+    #[inline]
+    pub(super) fn with_redis(mut self, url: impl Into<String>) -> Self {
+        self.state.redis_url = Some(url.into());
+        self
+    }
+
+    pub(super) async fn build(self) -> color_eyre::Result<&'static Shared<S>> {
+        let store = self.state.store.await?;
         let cache = moka::sync::Cache::builder()
-            .max_capacity(1000)
+            .max_capacity(self.state.cache_capacity)
+            .expire_after(ExpiryPolicy)
             .build_with_hasher(ahash::RandomState::new());
-        Ok(Box::leak(Box::new(Self {
-            pool,
+
+        let redis = match self.state.redis_url {
+            Some(url) => {
+                let redis = RedisCache::connect(&url).await?;
+                // Keep the local cache coherent with edits from other instances.
+                tokio::spawn(super::redis::run_invalidation_listener(url, cache.clone()));
+                Some(redis)
+            }
+            None => None,
+        };
+
+        let (events, _) = broadcast::channel(128);
+
+        Ok(Box::leak(Box::new(Shared {
+            store,
             cache,
             metrics: Metrics::default(),
+            redis,
+            events,
         })))
     }
 }
 
 #[instrument(skip_all, fields(%slug))]
-#[debug_handler]
-pub(super) async fn resolve(
+pub(super) async fn resolve<S: Store>(
     State(Shared {
-        pool,
+        store,
         cache,
         metrics,
-    }): State<&'static Shared>,
+        redis,
+        ..
+    }): State<&'static Shared<S>>,
     Path(slug): Path<Slug>,
 ) -> Result<Response, StatusCode> {
     // All requests are counted no matter their outcome
@@ -68,30 +211,54 @@ pub(super) async fn resolve(
         })
         .inc();
 
-    // Fast-path cache hits
-    if let Some((url, hidden)) = cache.get(&slug) {
+    // Fast-path L1 (moka) cache hits
+    if let Some((url, hidden, expires_at)) = cache.get(&slug) {
+        if is_expired(expires_at) {
+            cache.invalidate(&slug);
+            if let Some(redis) = redis {
+                redis.del(slug).await;
+            }
+            metrics.expired_resolutions.inc();
+            return Err(StatusCode::NOT_FOUND);
+        }
         return Ok(create_redirect(&url, hidden));
     }
     metrics.cache_misses.inc();
 
-    let row = sqlx::query!(
-        "SELECT url, hidden FROM links WHERE slug = $1",
-        slug.as_str()
-    )
-    .fetch_optional(pool)
-    .await;
-
-    match row {
-        Ok(Some(row)) => {
-            let resp = create_redirect(&row.url, row.hidden);
-            cache.insert(slug, (row.url.into(), row.hidden));
-            Ok(resp)
+    // Fall back to the optional L2 (Redis) cache, populating L1 on the way up.
+    if let Some(redis) = redis {
+        if let Some((url, hidden, expires_at)) = redis.get(slug).await {
+            metrics.redis_hits.inc();
+            if is_expired(expires_at) {
+                cache.invalidate(&slug);
+                redis.del(slug).await;
+                metrics.expired_resolutions.inc();
+                return Err(StatusCode::NOT_FOUND);
+            }
+            let resp = create_redirect(&url, hidden);
+            cache.insert(slug, (url, hidden, expires_at));
+            return Ok(resp);
         }
-        Ok(None) => Err(StatusCode::NOT_FOUND),
-        Err(e) => {
-            tracing::error!(cause = %e, "unable to resolve slug");
-            Err(StatusCode::SERVICE_UNAVAILABLE)
+        metrics.redis_misses.inc();
+    }
+
+    match store.resolve(slug).await {
+        Some((url, hidden, expires_at)) => {
+            if is_expired(expires_at) {
+                if let Some(redis) = redis {
+                    redis.del(slug).await;
+                }
+                metrics.expired_resolutions.inc();
+                return Err(StatusCode::NOT_FOUND);
+            }
+            let resp = create_redirect(&url, hidden);
+            if let Some(redis) = redis {
+                redis.set(slug, &url, hidden, expires_at).await;
+            }
+            cache.insert(slug, (url, hidden, expires_at));
+            Ok(resp)
         }
+        None => Err(StatusCode::NOT_FOUND),
     }
 }
 
@@ -107,32 +274,22 @@ fn create_redirect(url: &str, hidden: bool) -> Response {
 }
 
 #[instrument(skip_all)]
-#[debug_handler]
-pub(super) async fn reverse(
-    State(Shared { pool, metrics, .. }): State<&'static Shared>,
+pub(super) async fn reverse<S: Store>(
+    State(Shared { store, metrics, .. }): State<&'static Shared<S>>,
     Path(url): Path<Url>,
 ) -> Result<String, StatusCode> {
-    let slug = sqlx::query_scalar!("SELECT slug FROM links WHERE url = $1", url.as_str())
-        .fetch_optional(pool)
-        .await;
-
-    match slug {
-        Ok(Some(slug)) => {
+    match store.reverse(url.as_str()).await {
+        Some(slug) => {
             metrics
                 .http_requests
                 .get_or_create(&Labels {
                     handler: "reverse",
-                    // Slugs should always be correct length.
-                    slug: Some(Slug::try_from(slug.as_str()).unwrap()),
+                    slug: Some(slug),
                 })
                 .inc();
-            Ok(slug)
-        }
-        Ok(None) => Err(StatusCode::NOT_FOUND),
-        Err(e) => {
-            tracing::error!(cause = %e, "unable to reverse lookup");
-            Err(StatusCode::SERVICE_UNAVAILABLE)
+            Ok(slug.to_string())
         }
+        None => Err(StatusCode::NOT_FOUND),
     }
 }
 
@@ -141,59 +298,174 @@ pub(super) struct RegisterForm {
     url: String,
     #[serde(default)]
     hidden: bool,
+    // Optional time-to-live in seconds after which the link expires.
+    ttl: Option<u64>,
 }
 
 #[instrument(skip_all)]
-#[debug_handler]
-pub(super) async fn register(
-    State(Shared { pool, cache, .. }): State<&'static Shared>,
+pub(super) async fn register<S: Store>(
+    State(Shared {
+        store,
+        cache,
+        redis,
+        events,
+        ..
+    }): State<&'static Shared<S>>,
     Host(host): Host,
     Form(form): Form<RegisterForm>,
 ) -> Result<String, StatusCode> {
     let Ok(url) = Url::parse(&form.url) else {
         return Err(StatusCode::BAD_REQUEST);
     };
+    let expires_at = expiry_from_ttl(form.ttl);
     let mut retries = 0;
 
     loop {
         let slug = Slug::from_rng(&mut rand::thread_rng());
 
-        let result = sqlx::query!(
-            "INSERT INTO links (slug, url, hidden) VALUES ($1, $2, $3)",
-            slug.as_str(),
-            url.as_str(),
-            form.hidden,
-        )
-        .execute(pool)
-        .await;
-
-        break match result {
-            Ok(_) => {
+        break match store.insert(slug, url.as_str(), form.hidden, expires_at).await {
+            Ok(()) => {
                 tracing::debug!(%slug, "created");
-                cache.insert(slug, (String::from(url).into(), form.hidden));
+                if let Some(redis) = redis {
+                    redis.set(slug, url.as_str(), form.hidden, expires_at).await;
+                    redis.publish_invalidation(slug).await;
+                }
+                // Notify any live `/admin/events` subscribers; errors just mean
+                // nobody is currently listening.
+                let _ = events.send(Event {
+                    slug: slug.to_string(),
+                    url: url.as_str().to_owned(),
+                    hidden: form.hidden,
+                });
+                cache.insert(slug, (String::from(url).into(), form.hidden, expires_at));
                 // There is probably a better way to do this, but I can't be asked.
                 Ok(format!("http://{host}/{slug}"))
             }
-            Err(e) => Err(match e.as_database_error().and_then(|e| e.constraint()) {
-                Some("links_pkey") if retries < 2 => {
-                    tracing::debug!(%retries, "retrying");
-                    retries += 1;
-                    continue;
-                }
-                Some("links_url_key") => StatusCode::CONFLICT,
-                _ => {
+            Err(InsertError::SlugCollision) if retries < 2 => {
+                tracing::debug!(%retries, "retrying");
+                retries += 1;
+                continue;
+            }
+            Err(InsertError::UrlConflict) => Err(StatusCode::CONFLICT),
+            Err(e) => {
+                if let InsertError::Backend(e) = &e {
                     tracing::error!(cause = %e, "unable to generate link");
-                    StatusCode::SERVICE_UNAVAILABLE
                 }
-            }),
+                Err(StatusCode::SERVICE_UNAVAILABLE)
+            }
         };
     }
 }
 
+#[derive(Deserialize)]
+pub(super) struct BatchItem {
+    url: String,
+    #[serde(default)]
+    hidden: bool,
+    // Optional time-to-live in seconds after which the link expires.
+    ttl: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+pub(super) struct BatchOutcome {
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slug: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+}
+
+impl BatchOutcome {
+    fn created(url: String, slug: String) -> Self {
+        Self {
+            url,
+            slug: Some(slug),
+            error: None,
+        }
+    }
+
+    fn failed(url: String, error: &'static str) -> Self {
+        Self {
+            url,
+            slug: None,
+            error: Some(error),
+        }
+    }
+}
+
 #[instrument(skip_all)]
-#[debug_handler]
-pub(super) async fn admin_metrics(
-    State(Shared { metrics, .. }): State<&'static Shared>,
+pub(super) async fn batch<S: Store>(
+    State(Shared {
+        store,
+        cache,
+        metrics,
+        redis,
+        events,
+    }): State<&'static Shared<S>>,
+    Json(items): Json<Vec<BatchItem>>,
+) -> Json<Vec<BatchOutcome>> {
+    // A batch counts as a single request so large imports don't swamp the
+    // per-slug request metrics; item counts are tracked separately.
+    metrics
+        .http_requests
+        .get_or_create(&Labels {
+            handler: "batch",
+            slug: None,
+        })
+        .inc();
+
+    // Validate urls up front; invalid entries never reach the transaction.
+    let mut outcomes: Vec<Option<BatchOutcome>> = (0..items.len()).map(|_| None).collect();
+    let mut valid = Vec::with_capacity(items.len());
+    for (i, item) in items.into_iter().enumerate() {
+        match Url::parse(&item.url) {
+            Ok(url) => valid.push((i, url, item.hidden, expiry_from_ttl(item.ttl))),
+            Err(_) => {
+                metrics.batch_failed.inc();
+                outcomes[i] = Some(BatchOutcome::failed(item.url, "bad_request"));
+            }
+        }
+    }
+
+    let to_insert: Vec<super::store::BatchItem> = valid
+        .iter()
+        .map(|(_, url, hidden, expires_at)| (url.as_str().to_owned(), *hidden, *expires_at))
+        .collect();
+    let results = store.insert_batch(&to_insert).await;
+
+    for ((i, url, hidden, expires_at), result) in valid.into_iter().zip(results) {
+        outcomes[i] = Some(match result {
+            Ok(slug) => {
+                metrics.batch_inserted.inc();
+                if let Some(redis) = redis {
+                    redis.set(slug, url.as_str(), hidden, expires_at).await;
+                    redis.publish_invalidation(slug).await;
+                }
+                let _ = events.send(Event {
+                    slug: slug.to_string(),
+                    url: url.as_str().to_owned(),
+                    hidden,
+                });
+                cache.insert(slug, (url.as_str().into(), hidden, expires_at));
+                BatchOutcome::created(url.to_string(), slug.to_string())
+            }
+            Err(InsertError::UrlConflict) => {
+                metrics.batch_failed.inc();
+                BatchOutcome::failed(url.to_string(), "conflict")
+            }
+            Err(_) => {
+                metrics.batch_failed.inc();
+                BatchOutcome::failed(url.to_string(), "error")
+            }
+        });
+    }
+
+    Json(outcomes.into_iter().flatten().collect())
+}
+
+#[instrument(skip_all)]
+pub(super) async fn admin_metrics<S: Store>(
+    State(Shared { metrics, .. }): State<&'static Shared<S>>,
 ) -> Result<String, StatusCode> {
     let mut buffer = String::with_capacity(4096);
     let res = prometheus_client::encoding::text::encode(&mut buffer, metrics);
@@ -207,9 +479,8 @@ pub(super) async fn admin_metrics(
 }
 
 #[instrument(skip_all)]
-#[debug_handler]
 pub(super) async fn admin_list(
-    State(Shared { pool, .. }): State<&'static Shared>,
+    State(Shared { store, .. }): State<&'static Shared<PostgresStore>>,
 ) -> StreamBody<impl Stream<Item = sqlx::Result<String>>> {
     #[derive(Serialize)]
     struct Row<'a> {
@@ -219,7 +490,7 @@ pub(super) async fn admin_list(
     }
 
     sqlx::query!("SELECT slug, url, hidden FROM links")
-        .fetch(pool)
+        .fetch(store.pool())
         .map_ok(|row| {
             let mut s = serde_json::to_string(&Row {
                 slug: &row.slug,
@@ -232,3 +503,60 @@ pub(super) async fn admin_list(
         })
         .into()
 }
+
+#[instrument(skip_all)]
+pub(super) async fn admin_events<S: Store>(
+    State(Shared { events, .. }): State<&'static Shared<S>>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let rx = events.subscribe();
+    ws.on_upgrade(move |socket| forward_events(socket, rx))
+}
+
+// Forward broadcast events to a single client as JSON text frames until either
+// the socket closes or this subscriber is dropped. Lag (a slow client falling
+// behind the channel) is skipped rather than fatal.
+async fn forward_events(mut socket: WebSocket, mut rx: broadcast::Receiver<Event>) {
+    loop {
+        tokio::select! {
+            // A new registration to push out to this client.
+            received = rx.recv() => {
+                let event = match received {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::debug!(%n, "events subscriber lagged");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let Ok(text) = serde_json::to_string(&event) else {
+                    continue;
+                };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            // Drain inbound frames so a Close (or a dropped socket) is noticed
+            // promptly instead of lingering until the next broadcast send.
+            inbound = socket.recv() => match inbound {
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(_)) => break,
+            },
+        }
+    }
+}
+
+mod states {
+    use super::*;
+
+    #[doc(hidden)]
+    pub(super) struct Init;
+
+    #[doc(hidden)]
+    pub(super) struct HasStore<S> {
+        pub(super) store: Pin<Box<dyn Future<Output = color_eyre::Result<S>>>>,
+        pub(super) cache_capacity: u64,
+        pub(super) redis_url: Option<String>,
+    }
+}
@@ -14,6 +14,12 @@ pub(super) struct Metrics {
     // Metric families
     pub(super) http_requests: Family<Labels, Counter>,
     pub(super) cache_misses: Counter,
+    pub(super) redis_hits: Counter,
+    pub(super) redis_misses: Counter,
+    pub(super) auth_failures: Family<AuthLabels, Counter>,
+    pub(super) batch_inserted: Counter,
+    pub(super) batch_failed: Counter,
+    pub(super) expired_resolutions: Counter,
 }
 
 impl Default for Metrics {
@@ -21,6 +27,12 @@ impl Default for Metrics {
         let mut registry = Registry::default();
         let http_requests = Family::<Labels, Counter>::default();
         let cache_misses = Counter::default();
+        let redis_hits = Counter::default();
+        let redis_misses = Counter::default();
+        let auth_failures = Family::<AuthLabels, Counter>::default();
+        let batch_inserted = Counter::default();
+        let batch_failed = Counter::default();
+        let expired_resolutions = Counter::default();
         registry.register(
             "linker_http_requests",
             "Number of handled HTTP requests",
@@ -31,12 +43,48 @@ impl Default for Metrics {
             "Number of cache misses resolving slugs",
             cache_misses.clone(),
         );
+        registry.register(
+            "linker_redis_hits",
+            "Number of Redis L2 cache hits resolving slugs",
+            redis_hits.clone(),
+        );
+        registry.register(
+            "linker_redis_misses",
+            "Number of Redis L2 cache misses resolving slugs",
+            redis_misses.clone(),
+        );
+        registry.register(
+            "linker_auth_failures",
+            "Number of rejected requests by cause",
+            auth_failures.clone(),
+        );
+        registry.register(
+            "linker_batch_inserted",
+            "Number of links created through the batch endpoint",
+            batch_inserted.clone(),
+        );
+        registry.register(
+            "linker_batch_failed",
+            "Number of batch items that could not be created",
+            batch_failed.clone(),
+        );
+        registry.register(
+            "linker_expired_resolutions",
+            "Number of times an expired slug was requested",
+            expired_resolutions.clone(),
+        );
         let registry = Arc::new(registry);
 
         Self {
             registry,
             http_requests,
             cache_misses,
+            redis_hits,
+            redis_misses,
+            auth_failures,
+            batch_inserted,
+            batch_failed,
+            expired_resolutions,
         }
     }
 }
@@ -56,6 +104,11 @@ pub(super) struct Labels {
     pub(super) slug: Option<Slug>,
 }
 
+#[derive(Clone, PartialEq, Eq, Hash, Debug, EncodeLabelSet)]
+pub(super) struct AuthLabels {
+    pub(super) reason: &'static str,
+}
+
 impl EncodeLabelValue for Slug {
     fn encode(&self, encoder: &mut LabelValueEncoder<'_>) -> Result<(), std::fmt::Error> {
         self.as_str().encode(encoder)
@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+
+use super::{
+    handlers::Cache,
+    slug::Slug,
+    store::{self, Entry},
+};
+
+/// Pub/sub channel slugs are published on when their entry changes so every
+/// instance can drop its local moka copy.
+pub(super) const INVALIDATE_CHANNEL: &str = "linker:invalidate";
+
+/// Optional L2 cache and invalidation bus backed by Redis, shared by every
+/// `linker` instance behind a load balancer.
+#[derive(Clone)]
+pub(super) struct RedisCache {
+    manager: redis::aio::ConnectionManager,
+}
+
+impl RedisCache {
+    pub(super) async fn connect(url: &str) -> color_eyre::Result<Self> {
+        let client = redis::Client::open(url)?;
+        let manager = client.get_tokio_connection_manager().await?;
+        Ok(Self { manager })
+    }
+
+    /// Look an entry up in Redis, returning its [`Entry`].
+    pub(super) async fn get(&self, slug: Slug) -> Option<Entry> {
+        let mut conn = self.manager.clone();
+        match conn.get::<_, Option<Vec<u8>>>(slug.as_str()).await {
+            Ok(Some(value)) => store::unpack(&value),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::error!(cause = %e, "unable to read from redis");
+                None
+            }
+        }
+    }
+
+    /// Store an entry, mirroring the packed layout used by the embedded
+    /// backend. An expiry is also set as the Redis key TTL so stale entries
+    /// drop out of the L2 cache on their own.
+    pub(super) async fn set(
+        &self,
+        slug: Slug,
+        url: &str,
+        hidden: bool,
+        expires_at: Option<DateTime<Utc>>,
+    ) {
+        // Never cache an already-expired entry; it would only linger in L2.
+        if expires_at.is_some_and(|t| t <= Utc::now()) {
+            return;
+        }
+
+        let value = store::pack(hidden, url, expires_at);
+        let mut conn = self.manager.clone();
+
+        // When an expiry is present it is always mirrored as a Redis key TTL
+        // (at least a second) so stale entries drop out of L2 on their own.
+        let result = match expires_at {
+            Some(at) => {
+                let ttl = (at - Utc::now()).num_seconds().max(1) as u64;
+                conn.set_ex::<_, _, ()>(slug.as_str(), value, ttl).await
+            }
+            None => conn.set::<_, _, ()>(slug.as_str(), value).await,
+        };
+        if let Err(e) = result {
+            tracing::error!(cause = %e, "unable to write to redis");
+        }
+    }
+
+    /// Drop a slug from the L2 cache, e.g. once it is found expired on resolve.
+    pub(super) async fn del(&self, slug: Slug) {
+        let mut conn = self.manager.clone();
+        if let Err(e) = conn.del::<_, ()>(slug.as_str()).await {
+            tracing::error!(cause = %e, "unable to delete from redis");
+        }
+    }
+
+    /// Announce that a slug's entry changed so other instances invalidate it.
+    pub(super) async fn publish_invalidation(&self, slug: Slug) {
+        let mut conn = self.manager.clone();
+        if let Err(e) = conn.publish::<_, _, ()>(INVALIDATE_CHANNEL, slug.as_str()).await {
+            tracing::error!(cause = %e, "unable to publish invalidation");
+        }
+    }
+}
+
+/// Background task that invalidates the local moka cache whenever another
+/// instance publishes a slug on [`INVALIDATE_CHANNEL`].
+///
+/// The subscription is wrapped in a reconnect loop with capped backoff so a
+/// dropped pub/sub connection (restart, failover, network blip) doesn't
+/// silently stop cross-instance invalidation for the life of the process.
+pub(super) async fn run_invalidation_listener(url: String, cache: Cache) -> color_eyre::Result<()> {
+    use std::time::Duration;
+
+    let client = redis::Client::open(url)?;
+    let mut backoff = Duration::from_millis(100);
+    const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    loop {
+        match listen(&client, &cache).await {
+            // A clean stream end still means the connection is gone; reconnect.
+            Ok(()) => tracing::warn!("redis invalidation stream ended, reconnecting"),
+            Err(e) => tracing::error!(cause = %e, "redis invalidation listener dropped, reconnecting"),
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Subscribe once and forward invalidations until the connection drops.
+async fn listen(client: &redis::Client, cache: &Cache) -> color_eyre::Result<()> {
+    let mut pubsub = client.get_async_connection().await?.into_pubsub();
+    pubsub.subscribe(INVALIDATE_CHANNEL).await?;
+
+    let mut messages = pubsub.on_message();
+    while let Some(msg) = messages.next().await {
+        let Ok(payload) = msg.get_payload::<String>() else {
+            continue;
+        };
+        if let Ok(slug) = Slug::try_from(payload.as_str()) {
+            tracing::debug!(%slug, "invalidating");
+            cache.invalidate(&slug);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,481 @@
+use std::{future::Future, sync::Arc};
+
+use chrono::{DateTime, Utc};
+
+use super::{auth::Scope, slug::Slug};
+
+/// A resolved link: its url, whether it is hidden, and an optional expiry.
+pub(super) type Entry = (Arc<str>, bool, Option<DateTime<Utc>>);
+
+/// Reason an [`insert`](Store::insert) could not be committed.
+///
+/// `generate`/`register` rely on telling a slug collision (retryable) apart
+/// from a url conflict (a `409` for the caller), so the two uniqueness
+/// violations get their own variants; everything else is an opaque backend
+/// failure.
+pub(super) enum InsertError {
+    /// The slug is already taken (`links_pkey`), retry with a fresh one.
+    SlugCollision,
+    /// The url is already registered under another slug (`links_url_key`).
+    UrlConflict,
+    /// The backend failed for some other reason.
+    Backend(anyhow::Error),
+}
+
+/// The storage operations the handlers, the expiry sweeper and the auth
+/// middleware need.
+///
+/// Abstracting these lets the crate run either against Postgres or the
+/// self-contained embedded [`SledStore`], selected through the builder.
+///
+/// The methods spell out `-> impl Future + Send` rather than using bare
+/// `async fn` so the futures are usable from axum's `Send`-bounded handlers.
+pub(super) trait Store: Send + Sync + 'static {
+    /// Insert a new `slug -> (url, hidden, expires_at)` mapping, enforcing both
+    /// the slug and url uniqueness constraints.
+    fn insert(
+        &self,
+        slug: Slug,
+        url: &str,
+        hidden: bool,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> impl Future<Output = Result<(), InsertError>> + Send;
+
+    /// Resolve a slug to its [`Entry`], or `None` if unknown.
+    fn resolve(&self, slug: Slug) -> impl Future<Output = Option<Entry>> + Send;
+
+    /// Reverse lookup the slug a url was registered under.
+    fn reverse(&self, url: &str) -> impl Future<Output = Option<Slug>> + Send;
+
+    /// Delete every link whose expiry has passed. Backends default to a no-op.
+    fn delete_expired(&self) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    /// Whether this backend has an API-key store. Auth middleware is only
+    /// layered onto the write/admin routes when it does, so the embedded
+    /// backend stays a self-contained binary that needs no key provisioning.
+    fn has_key_store(&self) -> bool {
+        false
+    }
+
+    /// Look an API key up by its SHA-256 hash. Backends without a key store
+    /// default to denying every request.
+    fn lookup_key(&self, _hash: &[u8]) -> impl Future<Output = Option<KeyRecord>> + Send {
+        async { None }
+    }
+
+    /// Allocate a fresh slug for `url`, retrying on slug collisions just like
+    /// the single `register` path.
+    fn generate(
+        &self,
+        url: &str,
+        hidden: bool,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> impl Future<Output = Result<Slug, InsertError>> + Send {
+        async move {
+            let mut retries = 0;
+            loop {
+                let slug = Slug::from_rng(&mut rand::thread_rng());
+                match self.insert(slug, url, hidden, expires_at).await {
+                    Ok(()) => return Ok(slug),
+                    Err(InsertError::SlugCollision) if retries < 2 => {
+                        retries += 1;
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+
+    /// Generate a slug for every `(url, hidden, expires_at)` item, returning a
+    /// per-item result so partial success can be reported precisely. The
+    /// default implementation inserts items independently; backends that can
+    /// should override it to run the whole batch in a single transaction.
+    fn insert_batch(
+        &self,
+        items: &[BatchItem],
+    ) -> impl Future<Output = Vec<Result<Slug, InsertError>>> + Send {
+        async move {
+            let mut results = Vec::with_capacity(items.len());
+            for (url, hidden, expires_at) in items {
+                results.push(self.generate(url, *hidden, *expires_at).await);
+            }
+            results
+        }
+    }
+}
+
+/// A single entry to create through [`Store::insert_batch`].
+pub(super) type BatchItem = (String, bool, Option<DateTime<Utc>>);
+
+/// Pack an [`Entry`]'s metadata for the byte-oriented backends (sled, Redis):
+/// a leading `hidden` flag, eight big-endian bytes of expiry (`i64::MIN` when
+/// absent), then the url.
+pub(super) fn pack(hidden: bool, url: &str, expires_at: Option<DateTime<Utc>>) -> Vec<u8> {
+    let millis = expires_at.map_or(i64::MIN, |t| t.timestamp_millis());
+    let mut buf = Vec::with_capacity(url.len() + 9);
+    buf.push(hidden as u8);
+    buf.extend_from_slice(&millis.to_be_bytes());
+    buf.extend_from_slice(url.as_bytes());
+    buf
+}
+
+/// Inverse of [`pack`], returning `None` on a value too short to hold the
+/// fixed header since the bytes can come from an external/foreign Redis key.
+pub(super) fn unpack(value: &[u8]) -> Option<Entry> {
+    if value.len() < 9 {
+        return None;
+    }
+    let hidden = value[0] != 0;
+    let millis = i64::from_be_bytes(value[1..9].try_into().unwrap());
+    let expires_at = (millis != i64::MIN)
+        .then(|| DateTime::from_timestamp_millis(millis))
+        .flatten();
+    let url = String::from_utf8_lossy(&value[9..]);
+    Some((Arc::from(url.as_ref()), hidden, expires_at))
+}
+
+/// A stored API key's validity window and granted scopes.
+pub(super) struct KeyRecord {
+    pub(super) not_before: DateTime<Utc>,
+    pub(super) not_after: DateTime<Utc>,
+    pub(super) scopes: Vec<Scope>,
+}
+
+/// [`Store`] backed by a Postgres connection pool.
+pub(super) struct PostgresStore {
+    pool: sqlx::PgPool,
+}
+
+impl PostgresStore {
+    #[inline]
+    pub(super) fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Borrow the underlying pool for queries that fall outside the [`Store`]
+    /// abstraction (e.g. the admin table dump).
+    #[inline]
+    pub(super) fn pool(&self) -> &sqlx::PgPool {
+        &self.pool
+    }
+}
+
+impl Store for PostgresStore {
+    fn has_key_store(&self) -> bool {
+        true
+    }
+
+    async fn insert(
+        &self,
+        slug: Slug,
+        url: &str,
+        hidden: bool,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(), InsertError> {
+        let result = sqlx::query!(
+            "INSERT INTO links (slug, url, hidden, expires_at) VALUES ($1, $2, $3, $4)",
+            slug.as_str(),
+            url,
+            hidden,
+            expires_at,
+        )
+        .execute(&self.pool)
+        .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(match e.as_database_error().and_then(|e| e.constraint()) {
+                Some("links_pkey") => InsertError::SlugCollision,
+                Some("links_url_key") => InsertError::UrlConflict,
+                _ => InsertError::Backend(e.into()),
+            }),
+        }
+    }
+
+    async fn resolve(&self, slug: Slug) -> Option<Entry> {
+        let row = sqlx::query!(
+            "SELECT url, hidden, expires_at FROM links WHERE slug = $1",
+            slug.as_str()
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        match row {
+            Ok(row) => row.map(|row| (row.url.into(), row.hidden, row.expires_at)),
+            Err(e) => {
+                tracing::error!(cause = %e, "unable to resolve slug");
+                None
+            }
+        }
+    }
+
+    async fn reverse(&self, url: &str) -> Option<Slug> {
+        let slug = sqlx::query_scalar!("SELECT slug FROM links WHERE url = $1", url)
+            .fetch_optional(&self.pool)
+            .await;
+
+        match slug {
+            // Slugs should always be correct length.
+            Ok(slug) => slug.map(|slug| Slug::try_from(slug.as_str()).unwrap()),
+            Err(e) => {
+                tracing::error!(cause = %e, "unable to reverse lookup");
+                None
+            }
+        }
+    }
+
+    async fn delete_expired(&self) {
+        let result = sqlx::query!("DELETE FROM links WHERE expires_at < now()")
+            .execute(&self.pool)
+            .await;
+
+        match result {
+            Ok(done) if done.rows_affected() > 0 => {
+                tracing::debug!(rows = done.rows_affected(), "swept expired links");
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!(cause = %e, "unable to sweep expired links"),
+        }
+    }
+
+    async fn insert_batch(&self, items: &[BatchItem]) -> Vec<Result<Slug, InsertError>> {
+        let mut tx = match self.pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => {
+                let e: anyhow::Error = e.into();
+                tracing::error!(cause = %e, "unable to start batch transaction");
+                return (0..items.len())
+                    .map(|_| Err(InsertError::Backend(anyhow::anyhow!("{e}"))))
+                    .collect();
+            }
+        };
+
+        let mut results = Vec::with_capacity(items.len());
+        for (url, hidden, expires_at) in items {
+            // Each item gets its own savepoint so one collision/conflict only
+            // rolls back that item, leaving the rest of the batch intact.
+            let mut retries = 0;
+            let result = loop {
+                let slug = Slug::from_rng(&mut rand::thread_rng());
+                let mut savepoint = match tx.begin().await {
+                    Ok(sp) => sp,
+                    Err(e) => break Err(InsertError::Backend(e.into())),
+                };
+                let outcome = sqlx::query!(
+                    "INSERT INTO links (slug, url, hidden, expires_at) VALUES ($1, $2, $3, $4)",
+                    slug.as_str(),
+                    url,
+                    hidden,
+                    expires_at,
+                )
+                .execute(&mut *savepoint)
+                .await;
+
+                match outcome {
+                    Ok(_) => match savepoint.commit().await {
+                        Ok(()) => break Ok(slug),
+                        Err(e) => break Err(InsertError::Backend(e.into())),
+                    },
+                    Err(e) => {
+                        let _ = savepoint.rollback().await;
+                        match e.as_database_error().and_then(|e| e.constraint()) {
+                            Some("links_pkey") if retries < 2 => {
+                                retries += 1;
+                                continue;
+                            }
+                            Some("links_url_key") => break Err(InsertError::UrlConflict),
+                            _ => break Err(InsertError::Backend(e.into())),
+                        }
+                    }
+                }
+            };
+            results.push(result);
+        }
+
+        if let Err(e) = tx.commit().await {
+            // Per-item savepoint releases are not durable until this outer
+            // COMMIT lands; if it fails the whole batch rolled back, so no item
+            // may be reported as created to the caller.
+            tracing::error!(cause = %e, "unable to commit batch transaction");
+            let e: anyhow::Error = e.into();
+            return (0..items.len())
+                .map(|_| Err(InsertError::Backend(anyhow::anyhow!("{e}"))))
+                .collect();
+        }
+
+        results
+    }
+
+    async fn lookup_key(&self, hash: &[u8]) -> Option<KeyRecord> {
+        let row = sqlx::query!(
+            "SELECT not_before, not_after, scopes FROM api_keys WHERE key_hash = $1",
+            hash,
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        match row {
+            Ok(Some(row)) => Some(KeyRecord {
+                not_before: row.not_before,
+                not_after: row.not_after,
+                scopes: row.scopes.iter().filter_map(|s| Scope::parse(s)).collect(),
+            }),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::error!(cause = %e, "unable to look up api key");
+                None
+            }
+        }
+    }
+}
+
+/// Self-contained [`Store`] backed by an embedded [`sled`] database.
+///
+/// Two keyspaces are kept in lockstep: `slugs` maps a slug to its packed
+/// `(hidden, expiry, url)` value and `urls` maps a url back to its slug.
+/// Inserts touch both inside a single transaction so the slug- and
+/// url-uniqueness invariants hold atomically, mirroring the
+/// `links_pkey`/`links_url_key` constraints.
+pub(super) struct SledStore {
+    slugs: sled::Tree,
+    urls: sled::Tree,
+}
+
+/// Abort reason threaded out of the insert transaction.
+enum Abort {
+    SlugCollision,
+    UrlConflict,
+}
+
+impl SledStore {
+    pub(super) fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let db = sled::open(path)?;
+        let slugs = db.open_tree("slugs")?;
+        let urls = db.open_tree("urls")?;
+        Ok(Self { slugs, urls })
+    }
+}
+
+impl Store for SledStore {
+    async fn insert(
+        &self,
+        slug: Slug,
+        url: &str,
+        hidden: bool,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(), InsertError> {
+        use sled::transaction::{abort, TransactionError};
+
+        let value = pack(hidden, url, expires_at);
+        let result = (&self.slugs, &self.urls).transaction(|(slugs, urls)| {
+            if slugs.get(slug.as_str().as_bytes())?.is_some() {
+                return abort(Abort::SlugCollision);
+            }
+            if urls.get(url.as_bytes())?.is_some() {
+                return abort(Abort::UrlConflict);
+            }
+            slugs.insert(slug.as_str().as_bytes(), value.as_slice())?;
+            urls.insert(url.as_bytes(), slug.as_str().as_bytes())?;
+            Ok(())
+        });
+
+        result.map_err(|e| match e {
+            TransactionError::Abort(Abort::SlugCollision) => InsertError::SlugCollision,
+            TransactionError::Abort(Abort::UrlConflict) => InsertError::UrlConflict,
+            TransactionError::Storage(e) => InsertError::Backend(e.into()),
+        })
+    }
+
+    async fn resolve(&self, slug: Slug) -> Option<Entry> {
+        match self.slugs.get(slug.as_str().as_bytes()) {
+            Ok(Some(value)) => unpack(&value),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::error!(cause = %e, "unable to resolve slug");
+                None
+            }
+        }
+    }
+
+    async fn reverse(&self, url: &str) -> Option<Slug> {
+        match self.urls.get(url.as_bytes()) {
+            // Slugs should always be correct length.
+            Ok(slug) => {
+                slug.map(|slug| Slug::try_from(String::from_utf8_lossy(&slug).as_ref()).unwrap())
+            }
+            Err(e) => {
+                tracing::error!(cause = %e, "unable to reverse lookup");
+                None
+            }
+        }
+    }
+
+    async fn delete_expired(&self) {
+        let now = Utc::now().timestamp_millis();
+        for item in self.slugs.iter() {
+            let Ok((slug, value)) = item else {
+                continue;
+            };
+            let Some((url, _, expires_at)) = unpack(&value) else {
+                continue;
+            };
+            if expires_at.is_some_and(|t| t.timestamp_millis() < now) {
+                let _ = (&self.slugs, &self.urls).transaction(|(slugs, urls)| {
+                    slugs.remove(&slug)?;
+                    urls.remove(url.as_bytes())?;
+                    Ok::<_, sled::transaction::ConflictableTransactionError>(())
+                });
+            }
+        }
+    }
+
+    async fn insert_batch(&self, items: &[BatchItem]) -> Vec<Result<Slug, InsertError>> {
+        use sled::transaction::TransactionError;
+
+        // The whole batch runs in one transaction touching both trees, so
+        // every successful insert commits atomically. Conflicting items are
+        // skipped with a per-item error rather than aborting the transaction,
+        // keeping the partial-success reporting the handler relies on.
+        let result = (&self.slugs, &self.urls).transaction(|(slugs, urls)| {
+            let mut results = Vec::with_capacity(items.len());
+            for (url, hidden, expires_at) in items {
+                if urls.get(url.as_bytes())?.is_some() {
+                    results.push(Err(InsertError::UrlConflict));
+                    continue;
+                }
+                let value = pack(*hidden, url, *expires_at);
+                // Retry slug collisions just like the single insert path.
+                let mut outcome = Err(InsertError::SlugCollision);
+                for _ in 0..3 {
+                    let slug = Slug::from_rng(&mut rand::thread_rng());
+                    if slugs.get(slug.as_str().as_bytes())?.is_some() {
+                        continue;
+                    }
+                    slugs.insert(slug.as_str().as_bytes(), value.as_slice())?;
+                    urls.insert(url.as_bytes(), slug.as_str().as_bytes())?;
+                    outcome = Ok(slug);
+                    break;
+                }
+                results.push(outcome);
+            }
+            Ok::<_, sled::transaction::ConflictableTransactionError>(results)
+        });
+
+        match result {
+            Ok(results) => results,
+            Err(e) => {
+                let e: anyhow::Error = match e {
+                    TransactionError::Storage(e) => e.into(),
+                    TransactionError::Abort(()) => anyhow::anyhow!("batch transaction aborted"),
+                };
+                tracing::error!(cause = %e, "unable to commit batch transaction");
+                (0..items.len())
+                    .map(|_| Err(InsertError::Backend(anyhow::anyhow!("{e}"))))
+                    .collect()
+            }
+        }
+    }
+}